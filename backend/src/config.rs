@@ -0,0 +1,46 @@
+use serde::Deserialize;
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// Tunables for the engine and server, loaded from `config.toml` at startup.
+/// Any field missing from the file (or the file itself being absent) falls
+/// back to the defaults below, so operators only need to override what they
+/// care about.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub chunk_count: u64,
+    pub max_concurrent_downloads: usize,
+    pub listen_addr: String,
+    pub download_dir: String,
+    pub reporter_interval_ms: u64,
+    pub bandwidth_limit_bytes_per_sec: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            chunk_count: 8,
+            max_concurrent_downloads: 4,
+            listen_addr: "0.0.0.0:3000".to_string(),
+            download_dir: "FF".to_string(),
+            reporter_interval_ms: 500,
+            bandwidth_limit_bytes_per_sec: None,
+        }
+    }
+}
+
+impl Config {
+    /// Loads `config.toml` from the working directory. Mirrors the UDP
+    /// tracker's `Configuration::load_file`: a missing or unparsable file
+    /// just means "use the defaults" rather than a hard failure.
+    pub fn load_file() -> Self {
+        match std::fs::read_to_string(CONFIG_FILE) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("Failed to parse {}: {} — using defaults", CONFIG_FILE, e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+}
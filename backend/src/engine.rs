@@ -1,18 +1,53 @@
-use reqwest::header::RANGE;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering}; // NEW: For thread-safe counting
 use std::sync::Arc;
-use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 use tokio::sync::{Mutex, Semaphore};
 
-pub fn get_download_dir() -> PathBuf {
+use crate::config::Config;
+use crate::downloader::{Downloader, HttpDownloader};
+use crate::events::{ProgressBroadcaster, ProgressEvent};
+use crate::throttle::SharedThrottle;
+
+/// Max attempts per chunk before the whole task is marked `DownloadStatus::Error`.
+const MAX_CHUNK_ATTEMPTS: u32 = 5;
+/// Backoff base/cap for retrying a chunk, borrowed from how BitTorrent
+/// clients back off reconnecting to a flaky peer.
+const BASE_BACKOFF_MS: u64 = 500;
+const MAX_BACKOFF_MS: u64 = 8_000;
+/// Per-request timeout for a single range GET.
+const CHUNK_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Read block size when hashing a completed file for integrity verification.
+const HASH_READ_BUF_SIZE: usize = 64 * 1024;
+
+/// Hashes `path` with SHA-256 in fixed-size blocks so memory use stays flat
+/// even for multi-gigabyte files, returning the lowercase hex digest.
+async fn hash_file_sha256(path: &Path) -> Result<String, String> {
+    let mut file = tokio::fs::File::open(path).await.map_err(|e| e.to_string())?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; HASH_READ_BUF_SIZE];
+
+    loop {
+        let read = file.read(&mut buf).await.map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn get_download_dir(config: &Config) -> PathBuf {
     let base_dirs = directories::UserDirs::new().expect("Could not find user directories");
     let mut path = base_dirs
         .download_dir()
         .expect("No download dir")
         .to_path_buf();
-    path.push("FF");
+    path.push(&config.download_dir);
     path
 }
 
@@ -25,6 +60,24 @@ pub struct DownloadTask {
     pub downloaded_bytes: u64,
     pub status: DownloadStatus,
     pub save_path: String,
+    #[serde(default)]
+    pub chunks: Vec<ChunkState>,
+    #[serde(default)]
+    pub expected_sha256: Option<String>,
+    /// Additional source URLs for this task. When several mirrors advertise
+    /// matching `ACCEPT_RANGES`/`CONTENT_LENGTH`, chunk workers are assigned
+    /// round-robin across `[url] + mirrors` to aggregate bandwidth.
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+}
+
+/// Tracks how much of a single byte range has been written to disk, so a
+/// restart can resume each chunk from `start + written` instead of from 0.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkState {
+    pub start: u64,
+    pub end: u64,
+    pub written: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -40,8 +93,20 @@ pub async fn start_multistream_download(
     url: String,
     task_id: String,
     state_updater: Arc<Mutex<Vec<DownloadTask>>>,
+    config: Arc<Config>,
+    scheduler: Arc<Semaphore>,
+    events: ProgressBroadcaster,
+    throttle: SharedThrottle,
 ) -> Result<(), String> {
-    let ff_dir = get_download_dir();
+    // Stay Pending (and off the network entirely) until the scheduler has a
+    // free slot. Dropping this permit on completion, error, or abort (pause)
+    // is what lets the next Pending task start.
+    let _scheduler_permit = scheduler
+        .acquire_owned()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let ff_dir = get_download_dir(&config);
     tokio::fs::create_dir_all(&ff_dir)
         .await
         .map_err(|e| e.to_string())?;
@@ -49,30 +114,83 @@ pub async fn start_multistream_download(
     let filename = url.split('/').last().unwrap_or("download.bin").to_string();
     let file_path = ff_dir.join(&filename);
 
+    let downloader: Arc<dyn Downloader> =
+        Arc::new(HttpDownloader::new(CHUNK_REQUEST_TIMEOUT).map_err(|e| e.to_string())?);
+
     // 1. Get Details
-    let client = reqwest::Client::new();
-    let head = client.head(&url).send().await.map_err(|e| e.to_string())?;
-
-    let content_length = head
-        .headers()
-        .get(reqwest::header::CONTENT_LENGTH)
-        .and_then(|ct| ct.to_str().ok())
-        .and_then(|ct| ct.parse::<u64>().ok())
-        .unwrap_or(0);
-
-    let accepts_ranges = head
-        .headers()
-        .get(reqwest::header::ACCEPT_RANGES)
-        .map(|v| v == "bytes")
-        .unwrap_or(false);
-
-    // 2. Initialize File
-    let mut file = tokio::fs::File::create(&file_path)
-        .await
-        .map_err(|e| e.to_string())?;
-    file.set_len(content_length)
-        .await
-        .map_err(|e| e.to_string())?;
+    let details = downloader.head(&url).await?;
+    let content_length = details.content_length;
+    let accepts_ranges = details.accepts_ranges;
+
+    // 2. Figure out whether we're resuming. Existing chunk state is only
+    // trustworthy if the partial file it describes is still on disk.
+    let (existing_chunks, expected_sha256, mirrors) = {
+        let tasks = state_updater.lock().await;
+        match tasks.iter().find(|t| t.id == task_id) {
+            Some(task) => (
+                task.chunks.clone(),
+                task.expected_sha256.clone(),
+                task.mirrors.clone(),
+            ),
+            None => (Vec::new(), None, Vec::new()),
+        }
+    };
+
+    // Build the rotation of mirrors chunk workers round-robin across. Only
+    // mirrors that serve the exact same ranged resource are trusted; a
+    // mismatched one is silently dropped from the rotation rather than
+    // corrupting the download.
+    let mut usable_mirrors = vec![url.clone()];
+    if accepts_ranges && content_length > 0 {
+        for mirror in &mirrors {
+            if let Ok(mirror_details) = downloader.head(mirror).await {
+                if mirror_details.accepts_ranges && mirror_details.content_length == content_length
+                {
+                    usable_mirrors.push(mirror.clone());
+                }
+            }
+        }
+    }
+
+    let file_exists = tokio::fs::metadata(&file_path).await.is_ok();
+    let resuming = accepts_ranges && !existing_chunks.is_empty() && file_exists;
+
+    // Only a fresh download truncates/creates the file. Resuming must never
+    // touch bytes that are already on disk.
+    if !resuming {
+        let mut file = tokio::fs::File::create(&file_path)
+            .await
+            .map_err(|e| e.to_string())?;
+        file.set_len(content_length)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    let chunk_count: u64 = config.chunk_count;
+    let chunks: Vec<ChunkState> = if resuming {
+        existing_chunks
+    } else if accepts_ranges && content_length > 0 {
+        let chunk_size = content_length / chunk_count;
+        (0..chunk_count)
+            .map(|i| {
+                let start = i * chunk_size;
+                let end = if i == chunk_count - 1 {
+                    content_length - 1
+                } else {
+                    (i + 1) * chunk_size - 1
+                };
+                ChunkState {
+                    start,
+                    end,
+                    written: 0,
+                }
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let seeded_bytes: u64 = chunks.iter().map(|c| c.written).sum();
 
     // 3. Update State (Start)
     {
@@ -81,30 +199,64 @@ pub async fn start_multistream_download(
             task.status = DownloadStatus::Downloading;
             task.total_size = Some(content_length);
             task.save_path = file_path.to_string_lossy().to_string();
-            // Reset bytes if restarting (simple resume)
-            task.downloaded_bytes = 0;
+            task.downloaded_bytes = seeded_bytes;
+            task.chunks = chunks.clone();
         }
     }
 
     // 4. Setup Progress Tracking
-    // We use an Atomic counter so all threads can update it cheaply
-    let progress_counter = Arc::new(AtomicU64::new(0));
+    // We use an Atomic counter so all threads can update it cheaply, seeded
+    // from whatever was already written to disk on a previous run.
+    let progress_counter = Arc::new(AtomicU64::new(seeded_bytes));
+    // Per-chunk written counters, mirrored into `DownloadTask::chunks` by the
+    // reporter on each tick so history.json tracks what's actually on disk.
+    let chunk_progress: Arc<Vec<AtomicU64>> = Arc::new(
+        chunks
+            .iter()
+            .map(|c| AtomicU64::new(c.written))
+            .collect(),
+    );
 
-    // SPAWN A REPORTER: Updates the global state every 500ms
+    // SPAWN A REPORTER: Updates the global state every `reporter_interval_ms`
+    // and pushes a progress snapshot to any SSE subscribers. The error and
+    // completion paths below additionally force a final sync from the
+    // atomics before returning. A pause (the registry aborting this task's
+    // handle) can't be intercepted the same way, so `written` for a paused
+    // task is only as fresh as the last tick — resuming just re-fetches that
+    // sliver of already-written bytes rather than losing any.
     let progress_clone = progress_counter.clone();
     let state_clone_reporter = state_updater.clone();
     let id_clone_reporter = task_id.clone();
+    let reporter_interval = Duration::from_millis(config.reporter_interval_ms);
+    let events_reporter = events.clone();
 
+    let chunk_progress_reporter = chunk_progress.clone();
     let reporter_handle = tokio::spawn(async move {
+        let mut previous_bytes = seeded_bytes;
         loop {
-            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+            tokio::time::sleep(reporter_interval).await;
             let current_bytes = progress_clone.load(Ordering::Relaxed);
+            let bytes_per_sec = (current_bytes.saturating_sub(previous_bytes) * 1000)
+                / reporter_interval.as_millis().max(1) as u64;
+            previous_bytes = current_bytes;
 
             let mut tasks = state_clone_reporter.lock().await;
             if let Some(task) = tasks.iter_mut().find(|t| t.id == id_clone_reporter) {
                 // Only update if we are still downloading
                 if task.status == DownloadStatus::Downloading {
                     task.downloaded_bytes = current_bytes;
+                    for (chunk_state, written) in
+                        task.chunks.iter_mut().zip(chunk_progress_reporter.iter())
+                    {
+                        chunk_state.written = written.load(Ordering::Relaxed);
+                    }
+                    let _ = events_reporter.send(ProgressEvent {
+                        id: task.id.clone(),
+                        status: task.status.clone(),
+                        downloaded_bytes: task.downloaded_bytes,
+                        total_size: task.total_size,
+                        bytes_per_sec,
+                    });
                 } else {
                     break; // Stop reporting if paused/cancelled
                 }
@@ -115,81 +267,313 @@ pub async fn start_multistream_download(
     if accepts_ranges && content_length > 0 {
         // --- MULTI STREAM ---
         println!("Starting multi-stream download for {}", filename);
-        let chunk_count = 8;
-        let chunk_size = content_length / chunk_count;
         let mut handles = vec![];
-        let sem = Arc::new(Semaphore::new(chunk_count as usize));
+        let sem = Arc::new(Semaphore::new(chunks.len()));
 
-        for i in 0..chunk_count {
-            let start = i * chunk_size;
-            let end = if i == chunk_count - 1 {
-                content_length - 1
-            } else {
-                (i + 1) * chunk_size - 1
-            };
+        for (i, chunk_state) in chunks.iter().enumerate() {
+            let start = chunk_state.start;
+            let end = chunk_state.end;
 
-            let url_clone = url.clone();
+            // Round-robin this chunk's primary mirror across the usable list.
+            let mirror_rotation = usable_mirrors.clone();
+            let mirror_start_idx = i % mirror_rotation.len();
             let path_clone = file_path.clone();
             let sem_clone = sem.clone();
             let progress_clone_worker = progress_counter.clone(); // Worker needs access to counter
+            let chunk_progress_worker = chunk_progress.clone();
+            let downloader_worker = downloader.clone();
+            let throttle_worker = throttle.clone();
 
             let handle = tokio::spawn(async move {
                 let _permit = sem_clone.acquire().await.unwrap();
-                let client = reqwest::Client::new();
-
-                // Request the Range
-                let mut response = client
-                    .get(&url_clone)
-                    .header(RANGE, format!("bytes={}-{}", start, end))
-                    .send()
-                    .await
-                    .unwrap();
-
-                let mut file = tokio::fs::OpenOptions::new()
-                    .write(true)
-                    .open(&path_clone)
-                    .await
-                    .unwrap();
-
-                file.seek(tokio::io::SeekFrom::Start(start)).await.unwrap();
-
-                // NEW: STREAMING LOGIC
-                // Read chunks as they arrive
-                while let Ok(Some(chunk)) = response.chunk().await {
-                    file.write_all(&chunk).await.unwrap();
-                    // Update the atomic counter immediately
-                    progress_clone_worker.fetch_add(chunk.len() as u64, Ordering::Relaxed);
-                }
+                download_chunk_with_retry(
+                    downloader_worker.as_ref(),
+                    &mirror_rotation,
+                    mirror_start_idx,
+                    &path_clone,
+                    start,
+                    end,
+                    i,
+                    &progress_clone_worker,
+                    &chunk_progress_worker,
+                    &throttle_worker,
+                )
+                .await
             });
             handles.push(handle);
         }
 
+        let mut chunk_error = None;
         for h in handles {
-            let _ = h.await;
+            if let Ok(Err(e)) = h.await {
+                chunk_error.get_or_insert(e);
+            }
+        }
+
+        if let Some(err) = chunk_error {
+            reporter_handle.abort();
+            let mut tasks = state_updater.lock().await;
+            if let Some(task) = tasks.iter_mut().find(|t| t.id == task_id) {
+                // Sync `written` from the atomics one last time so the error
+                // path doesn't leave chunk state stuck at whatever the
+                // reporter last observed up to `reporter_interval_ms` ago.
+                for (chunk_state, written) in task.chunks.iter_mut().zip(chunk_progress.iter()) {
+                    chunk_state.written = written.load(Ordering::Relaxed);
+                }
+                task.downloaded_bytes = progress_counter.load(Ordering::Relaxed);
+                task.status = DownloadStatus::Error(err.clone());
+                let _ = events.send(ProgressEvent {
+                    id: task.id.clone(),
+                    status: task.status.clone(),
+                    downloaded_bytes: task.downloaded_bytes,
+                    total_size: task.total_size,
+                    bytes_per_sec: 0,
+                });
+            }
+            return Err(err);
         }
     } else {
         // --- SINGLE STREAM ---
         println!("Falling back to single stream for {}", filename);
-        let mut resp = client.get(&url).send().await.map_err(|e| e.to_string())?;
         let progress_clone_worker = progress_counter.clone();
 
-        while let Ok(Some(chunk)) = resp.chunk().await {
-            file.write_all(&chunk).await.map_err(|e| e.to_string())?;
-            progress_clone_worker.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        if let Err(err) = download_plain_with_retry(
+            downloader.as_ref(),
+            &url,
+            &file_path,
+            &progress_clone_worker,
+            &throttle,
+        )
+        .await
+        {
+            reporter_handle.abort();
+            let mut tasks = state_updater.lock().await;
+            if let Some(task) = tasks.iter_mut().find(|t| t.id == task_id) {
+                task.downloaded_bytes = progress_counter.load(Ordering::Relaxed);
+                task.status = DownloadStatus::Error(err.clone());
+                let _ = events.send(ProgressEvent {
+                    id: task.id.clone(),
+                    status: task.status.clone(),
+                    downloaded_bytes: task.downloaded_bytes,
+                    total_size: task.total_size,
+                    bytes_per_sec: 0,
+                });
+            }
+            return Err(err);
         }
     }
 
     // 5. Cleanup
     reporter_handle.abort(); // Stop the background reporter
 
+    // Optional integrity check before we call it Completed
+    if let Some(expected) = expected_sha256 {
+        let actual = hash_file_sha256(&file_path).await.map_err(|e| e.to_string())?;
+        if !actual.eq_ignore_ascii_case(&expected) {
+            let message = format!("checksum mismatch: expected {} got {}", expected, actual);
+            let _ = tokio::fs::remove_file(&file_path).await;
+
+            let mut tasks = state_updater.lock().await;
+            if let Some(task) = tasks.iter_mut().find(|t| t.id == task_id) {
+                task.status = DownloadStatus::Error(message.clone());
+                let _ = events.send(ProgressEvent {
+                    id: task.id.clone(),
+                    status: task.status.clone(),
+                    downloaded_bytes: task.downloaded_bytes,
+                    total_size: task.total_size,
+                    bytes_per_sec: 0,
+                });
+            }
+            return Err(message);
+        }
+    }
+
     // Final Update to ensure 100%
     {
         let mut tasks = state_updater.lock().await;
         if let Some(task) = tasks.iter_mut().find(|t| t.id == task_id) {
             task.status = DownloadStatus::Completed;
             task.downloaded_bytes = content_length;
+            for chunk_state in task.chunks.iter_mut() {
+                chunk_state.written = chunk_state.end - chunk_state.start + 1;
+            }
+            let _ = events.send(ProgressEvent {
+                id: task.id.clone(),
+                status: task.status.clone(),
+                downloaded_bytes: task.downloaded_bytes,
+                total_size: task.total_size,
+                bytes_per_sec: 0,
+            });
         }
     }
 
     Ok(())
 }
+
+/// Downloads a single `[start, end]` byte range into `path`, retrying on any
+/// send/stream error with capped exponential backoff. A partial write from a
+/// failed attempt is kept (the file isn't truncated), so the next attempt
+/// resumes from `start + bytes_written_so_far` rather than redownloading the
+/// whole range. Each retry rotates to the next mirror in `mirrors`, so a
+/// mirror that fails a chunk hands that range to a different host rather
+/// than hammering the one that just failed.
+#[allow(clippy::too_many_arguments)]
+async fn download_chunk_with_retry(
+    downloader: &dyn Downloader,
+    mirrors: &[String],
+    mirror_start_idx: usize,
+    path: &Path,
+    start: u64,
+    end: u64,
+    chunk_index: usize,
+    progress_counter: &AtomicU64,
+    chunk_progress: &[AtomicU64],
+    throttle: &SharedThrottle,
+) -> Result<(), String> {
+    let mut last_err = String::new();
+    for attempt in 0..MAX_CHUNK_ATTEMPTS {
+        let resume_from = start + chunk_progress[chunk_index].load(Ordering::Relaxed);
+        if resume_from > end {
+            // Already fully written, either by a previous run or a previous attempt.
+            return Ok(());
+        }
+
+        let mirror_url = &mirrors[(mirror_start_idx + attempt as usize) % mirrors.len()];
+
+        match fetch_range_into_file(
+            downloader,
+            mirror_url,
+            path,
+            resume_from,
+            end,
+            progress_counter,
+            &chunk_progress[chunk_index],
+            throttle,
+        )
+        .await
+        {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                last_err = e;
+                if attempt + 1 < MAX_CHUNK_ATTEMPTS {
+                    let backoff_ms = (BASE_BACKOFF_MS * 2u64.pow(attempt)).min(MAX_BACKOFF_MS);
+                    tokio::time::sleep(Duration::from_millis(backoff_ms)).await;
+                }
+            }
+        }
+    }
+
+    Err(format!(
+        "chunk {}-{} failed after {} attempts: {}",
+        start, end, MAX_CHUNK_ATTEMPTS, last_err
+    ))
+}
+
+/// Downloads the whole resource in one stream for servers that don't
+/// support ranges, retrying on any request/stream error with the same
+/// capped backoff as `download_chunk_with_retry`. A non-ranged server gives
+/// us no offset to resume from, so each retry restarts from byte 0 and
+/// rolls the progress counter back to match, rather than silently finishing
+/// with a truncated file when a chunk error ends the stream early.
+async fn download_plain_with_retry(
+    downloader: &dyn Downloader,
+    url: &str,
+    path: &Path,
+    progress_counter: &AtomicU64,
+    throttle: &SharedThrottle,
+) -> Result<(), String> {
+    let mut last_err = String::new();
+    for attempt in 0..MAX_CHUNK_ATTEMPTS {
+        match download_plain_once(downloader, url, path, progress_counter, throttle).await {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                last_err = e;
+                progress_counter.store(0, Ordering::Relaxed);
+                if attempt + 1 < MAX_CHUNK_ATTEMPTS {
+                    let backoff_ms = (BASE_BACKOFF_MS * 2u64.pow(attempt)).min(MAX_BACKOFF_MS);
+                    tokio::time::sleep(Duration::from_millis(backoff_ms)).await;
+                }
+            }
+        }
+    }
+
+    Err(format!(
+        "single-stream download failed after {} attempts: {}",
+        MAX_CHUNK_ATTEMPTS, last_err
+    ))
+}
+
+async fn download_plain_once(
+    downloader: &dyn Downloader,
+    url: &str,
+    path: &Path,
+    progress_counter: &AtomicU64,
+    throttle: &SharedThrottle,
+) -> Result<(), String> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .await
+        .map_err(|e| e.to_string())?;
+    file.set_len(0).await.map_err(|e| e.to_string())?;
+    file.seek(tokio::io::SeekFrom::Start(0))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut response = downloader.fetch_full(url).await?;
+    loop {
+        match response.chunk().await {
+            Ok(Some(bytes)) => {
+                if let Some(t) = throttle {
+                    t.acquire(bytes.len() as u64).await;
+                }
+                file.write_all(&bytes).await.map_err(|e| e.to_string())?;
+                progress_counter.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+            }
+            Ok(None) => return Ok(()),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
+
+/// Issues a single `RANGE` GET for `[resume_from, end]` and streams it into
+/// `path` at the matching offset, bumping the progress counters as bytes
+/// land on disk.
+#[allow(clippy::too_many_arguments)]
+async fn fetch_range_into_file(
+    downloader: &dyn Downloader,
+    url: &str,
+    path: &Path,
+    resume_from: u64,
+    end: u64,
+    progress_counter: &AtomicU64,
+    chunk_progress: &AtomicU64,
+    throttle: &SharedThrottle,
+) -> Result<(), String> {
+    let mut response = downloader.fetch_range(url, resume_from, end).await?;
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    file.seek(tokio::io::SeekFrom::Start(resume_from))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    loop {
+        match response.chunk().await {
+            Ok(Some(bytes)) => {
+                if let Some(t) = throttle {
+                    t.acquire(bytes.len() as u64).await;
+                }
+                file.write_all(&bytes).await.map_err(|e| e.to_string())?;
+                progress_counter.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+                chunk_progress.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+            }
+            Ok(None) => return Ok(()),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
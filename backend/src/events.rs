@@ -0,0 +1,27 @@
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::engine::DownloadStatus;
+
+/// A progress snapshot for one task, pushed to SSE subscribers as it changes
+/// instead of waiting for the next poll.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressEvent {
+    pub id: String,
+    pub status: DownloadStatus,
+    pub downloaded_bytes: u64,
+    pub total_size: Option<u64>,
+    pub bytes_per_sec: u64,
+}
+
+/// Fan-out channel the engine's reporter publishes into on every tick and on
+/// status transitions; any number of `/api/downloads/events` subscribers get
+/// the same stream.
+pub type ProgressBroadcaster = broadcast::Sender<ProgressEvent>;
+
+const CHANNEL_CAPACITY: usize = 256;
+
+pub fn new_broadcaster() -> ProgressBroadcaster {
+    let (tx, _rx) = broadcast::channel(CHANNEL_CAPACITY);
+    tx
+}
@@ -0,0 +1,145 @@
+use axum::body::Body;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+struct RangeSpec {
+    start: u64,
+    end: u64,
+}
+
+/// Parses a single-range `Range: bytes=start-end` header (the only form we
+/// advertise support for via `Accept-Ranges: bytes`), including the suffix
+/// form `bytes=-N` ("the last N bytes") that real media players send.
+/// Returns `None` for anything malformed or out of bounds so the caller can
+/// fall back to a full `200` response.
+fn parse_range(header_value: &str, file_size: u64) -> Option<RangeSpec> {
+    let value = header_value.strip_prefix("bytes=")?;
+    let (start_str, end_str) = value.split_once('-')?;
+
+    let (start, end): (u64, u64) = if start_str.is_empty() {
+        // Suffix range: an empty start means "the last N bytes", not "from
+        // byte 0" — N is given in `end_str`.
+        let suffix_len: u64 = end_str.parse().ok()?;
+        if suffix_len == 0 {
+            return None;
+        }
+        (file_size.saturating_sub(suffix_len), file_size.saturating_sub(1))
+    } else {
+        let start: u64 = start_str.parse().ok()?;
+        let end: u64 = if end_str.is_empty() {
+            file_size.saturating_sub(1)
+        } else {
+            end_str.parse().ok()?
+        };
+        (start, end)
+    };
+
+    if start > end || end >= file_size {
+        return None;
+    }
+    Some(RangeSpec { start, end })
+}
+
+/// Streams `path` back to the client the way a media server does: honors an
+/// incoming `Range` header with `206 Partial Content` and a matching
+/// `Content-Range`, or falls back to a full `200` when absent/invalid.
+pub async fn serve_file(path: &str, headers: &HeaderMap) -> Response {
+    let metadata = match tokio::fs::metadata(path).await {
+        Ok(m) => m,
+        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let file_size = metadata.len();
+
+    let mut file = match tokio::fs::File::open(path).await {
+        Ok(f) => f,
+        Err(_) => return StatusCode::NOT_FOUND.into_response(),
+    };
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| parse_range(v, file_size));
+
+    match range {
+        Some(range) => {
+            if file
+                .seek(tokio::io::SeekFrom::Start(range.start))
+                .await
+                .is_err()
+            {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+            let len = range.end - range.start + 1;
+            let body = Body::wrap_stream(ReaderStream::new(file.take(len)));
+
+            Response::builder()
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_TYPE, "application/octet-stream")
+                .header(header::ACCEPT_RANGES, "bytes")
+                .header(header::CONTENT_LENGTH, len.to_string())
+                .header(
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", range.start, range.end, file_size),
+                )
+                .body(body)
+                .unwrap()
+                .into_response()
+        }
+        None => {
+            let body = Body::wrap_stream(ReaderStream::new(file));
+            Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, "application/octet-stream")
+                .header(header::ACCEPT_RANGES, "bytes")
+                .header(header::CONTENT_LENGTH, file_size.to_string())
+                .body(body)
+                .unwrap()
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_normal_range() {
+        let range = parse_range("bytes=100-199", 1_000).unwrap();
+        assert_eq!(range.start, 100);
+        assert_eq!(range.end, 199);
+    }
+
+    #[test]
+    fn parses_an_open_ended_range() {
+        let range = parse_range("bytes=900-", 1_000).unwrap();
+        assert_eq!(range.start, 900);
+        assert_eq!(range.end, 999);
+    }
+
+    #[test]
+    fn parses_a_suffix_range() {
+        // "the last 500 bytes" of a 1000-byte file is 500-999, not 0-500.
+        let range = parse_range("bytes=-500", 1_000).unwrap();
+        assert_eq!(range.start, 500);
+        assert_eq!(range.end, 999);
+    }
+
+    #[test]
+    fn suffix_range_longer_than_the_file_clamps_to_the_whole_file() {
+        let range = parse_range("bytes=-5000", 1_000).unwrap();
+        assert_eq!(range.start, 0);
+        assert_eq!(range.end, 999);
+    }
+
+    #[test]
+    fn rejects_out_of_bounds_and_malformed_ranges() {
+        assert!(parse_range("bytes=1000-1999", 1_000).is_none()); // end >= file_size
+        assert!(parse_range("bytes=500-100", 1_000).is_none()); // start > end
+        assert!(parse_range("bytes=-0", 1_000).is_none()); // zero-length suffix
+        assert!(parse_range("bytes=", 1_000).is_none());
+        assert!(parse_range("100-199", 1_000).is_none()); // missing "bytes=" prefix
+    }
+}
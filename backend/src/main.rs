@@ -1,31 +1,60 @@
 use axum::{
     extract::Path,
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive},
+        IntoResponse, Response, Sse,
+    },
     routing::{delete, get, post}, // Added delete
     Extension,
     Json,
     Router,
 };
 use dashmap::DashMap;
+use futures::Stream;
 use std::process::Command;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{Mutex, Semaphore};
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
 use tower_http::cors::{Any, CorsLayer};
 use uuid::Uuid; // NEW: Thread-safe map for control handles
 
+mod config;
+mod downloader;
 mod engine;
+mod events;
+mod files;
+mod share;
+mod throttle;
+use config::Config;
 use engine::{DownloadStatus, DownloadTask};
+use events::{ProgressBroadcaster, ProgressEvent};
+use share::ShareRegistry;
+use throttle::SharedThrottle;
 
 type AppState = Arc<Mutex<Vec<DownloadTask>>>;
 // NEW: A registry to hold the "Stop Button" for each active download
 type TaskRegistry = Arc<DashMap<String, tokio::task::AbortHandle>>;
+// Global admission control: only `max_concurrent_downloads` tasks may be
+// actively transferring at once. Everyone else sits in DownloadStatus::Pending
+// waiting on a permit.
+type DownloadScheduler = Arc<Semaphore>;
 
 const HISTORY_FILE: &str = "history.json";
 
 #[tokio::main]
 async fn main() {
+    let config = Arc::new(Config::load_file());
     let initial_data = load_history();
     let state: AppState = Arc::new(Mutex::new(initial_data));
     let registry: TaskRegistry = Arc::new(DashMap::new());
+    let scheduler: DownloadScheduler = Arc::new(Semaphore::new(config.max_concurrent_downloads));
+    let shares: ShareRegistry = Arc::new(DashMap::new());
+    let events: ProgressBroadcaster = events::new_broadcaster();
+    // Built once and shared by every download, so `bandwidth_limit_bytes_per_sec`
+    // caps the *aggregate* rate rather than being granted per-transfer.
+    let throttle: SharedThrottle = throttle::from_config(config.bandwidth_limit_bytes_per_sec);
 
     // Auto-save task
     let state_clone = state.clone();
@@ -48,8 +77,19 @@ async fn main() {
         // Control Routes
         .route("/api/downloads/:id/pause", post(pause_download))
         .route("/api/downloads/:id/resume", post(resume_download))
+        // Serving + Share Routes
+        .route("/api/downloads/:id/file", get(serve_download_file))
+        .route("/api/downloads/:id/share", post(create_share))
+        .route("/api/share/:token", get(serve_share))
+        // Live Progress
+        .route("/api/downloads/events", get(download_events))
         .layer(Extension(state))
         .layer(Extension(registry)) // Inject the registry
+        .layer(Extension(scheduler))
+        .layer(Extension(config.clone()))
+        .layer(Extension(shares))
+        .layer(Extension(events))
+        .layer(Extension(throttle))
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
@@ -57,8 +97,10 @@ async fn main() {
                 .allow_headers(Any),
         );
 
-    println!("Ferris Fetcher listening on localhost:3000");
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+    println!("Ferris Fetcher listening on {}", config.listen_addr);
+    let listener = tokio::net::TcpListener::bind(&config.listen_addr)
+        .await
+        .unwrap();
     axum::serve(listener, app).await.unwrap();
 }
 
@@ -69,6 +111,7 @@ async fn pause_download(
     Path(id): Path<String>,
     Extension(state): Extension<AppState>,
     Extension(registry): Extension<TaskRegistry>,
+    Extension(events): Extension<ProgressBroadcaster>,
 ) -> Json<String> {
     // Stop the thread
     if let Some((_, handle)) = registry.remove(&id) {
@@ -78,6 +121,13 @@ async fn pause_download(
     let mut tasks = state.lock().await;
     if let Some(task) = tasks.iter_mut().find(|t| t.id == id) {
         task.status = DownloadStatus::Paused;
+        let _ = events.send(ProgressEvent {
+            id: task.id.clone(),
+            status: task.status.clone(),
+            downloaded_bytes: task.downloaded_bytes,
+            total_size: task.total_size,
+            bytes_per_sec: 0,
+        });
     }
     Json("Paused".to_string())
 }
@@ -87,6 +137,10 @@ async fn resume_download(
     Path(id): Path<String>,
     Extension(state): Extension<AppState>,
     Extension(registry): Extension<TaskRegistry>,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(scheduler): Extension<DownloadScheduler>,
+    Extension(events): Extension<ProgressBroadcaster>,
+    Extension(throttle): Extension<SharedThrottle>,
 ) -> Json<String> {
     let mut tasks = state.lock().await;
     // Find the task data
@@ -98,13 +152,26 @@ async fn resume_download(
         let url = task.url.clone();
         let id_clone = id.clone();
         let state_clone = state.clone(); // We need a fresh clone of the Arc for the thread
+        let config_clone = config.clone();
+        let scheduler_clone = scheduler.clone();
+        let events_clone = events.clone();
+        let throttle_clone = throttle.clone();
 
         // Important: We drop the lock on 'tasks' here implicitly before spawning,
         // otherwise the download thread would deadlock waiting for us to finish.
         drop(tasks);
 
         let handle = tokio::spawn(async move {
-            let _ = engine::start_multistream_download(url, id_clone, state_clone).await;
+            let _ = engine::start_multistream_download(
+                url,
+                id_clone,
+                state_clone,
+                config_clone,
+                scheduler_clone,
+                events_clone,
+                throttle_clone,
+            )
+            .await;
         });
 
         // Save the new handle so we can pause it again
@@ -180,11 +247,19 @@ async fn list_downloads(Extension(state): Extension<AppState>) -> Json<Vec<Downl
 #[derive(serde::Deserialize)]
 struct CreateDownload {
     url: String,
+    #[serde(default)]
+    expected_sha256: Option<String>,
+    #[serde(default)]
+    mirrors: Vec<String>,
 }
 
 async fn add_download(
     Extension(state): Extension<AppState>,
     Extension(registry): Extension<TaskRegistry>,
+    Extension(config): Extension<Arc<Config>>,
+    Extension(scheduler): Extension<DownloadScheduler>,
+    Extension(events): Extension<ProgressBroadcaster>,
+    Extension(throttle): Extension<SharedThrottle>,
     Json(payload): Json<CreateDownload>,
 ) -> Json<DownloadTask> {
     let id = Uuid::new_v4().to_string();
@@ -196,6 +271,9 @@ async fn add_download(
         downloaded_bytes: 0,
         status: DownloadStatus::Pending,
         save_path: String::new(),
+        chunks: Vec::new(),
+        expected_sha256: payload.expected_sha256.clone(),
+        mirrors: payload.mirrors.clone(),
     };
 
     {
@@ -206,10 +284,23 @@ async fn add_download(
 
     let state_clone = state.clone();
     let id_clone = id.clone();
+    let config_clone = config.clone();
+    let scheduler_clone = scheduler.clone();
+    let events_clone = events.clone();
+    let throttle_clone = throttle.clone();
 
     // Spawn and capture handle
     let handle = tokio::spawn(async move {
-        let _ = engine::start_multistream_download(payload.url, id_clone, state_clone).await;
+        let _ = engine::start_multistream_download(
+            payload.url,
+            id_clone,
+            state_clone,
+            config_clone,
+            scheduler_clone,
+            events_clone,
+            throttle_clone,
+        )
+        .await;
     });
 
     // Store handle
@@ -234,3 +325,94 @@ async fn open_file_location(
     }
     Json("Opened".to_string())
 }
+
+// --- FILE SERVING + SHARE LINKS ---
+
+async fn serve_download_file(
+    Path(id): Path<String>,
+    Extension(state): Extension<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    let tasks = state.lock().await;
+    match tasks.iter().find(|t| t.id == id) {
+        Some(task) if task.status == DownloadStatus::Completed => {
+            files::serve_file(&task.save_path, &headers).await
+        }
+        Some(_) => (StatusCode::CONFLICT, "Download is not completed yet").into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct CreateShare {
+    #[serde(default)]
+    ttl_secs: Option<u64>,
+    #[serde(default)]
+    max_downloads: Option<u32>,
+}
+
+#[derive(serde::Serialize)]
+struct ShareResponse {
+    token: String,
+}
+
+async fn create_share(
+    Path(id): Path<String>,
+    Extension(state): Extension<AppState>,
+    Extension(shares): Extension<ShareRegistry>,
+    Json(payload): Json<CreateShare>,
+) -> Result<Json<ShareResponse>, StatusCode> {
+    let tasks = state.lock().await;
+    let task = tasks
+        .iter()
+        .find(|t| t.id == id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if task.status != DownloadStatus::Completed {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let ttl_secs = payload.ttl_secs.unwrap_or(share::DEFAULT_TTL_SECS);
+    let token = share::mint(&shares, task.id.clone(), ttl_secs, payload.max_downloads);
+    Ok(Json(ShareResponse { token }))
+}
+
+async fn serve_share(
+    Path(token): Path<String>,
+    Extension(state): Extension<AppState>,
+    Extension(shares): Extension<ShareRegistry>,
+    headers: HeaderMap,
+) -> Response {
+    let task_id = match share::consume(&shares, &token) {
+        Ok(task_id) => task_id,
+        Err(share::ShareError::Gone) => return StatusCode::GONE.into_response(),
+    };
+
+    let tasks = state.lock().await;
+    match tasks.iter().find(|t| t.id == task_id) {
+        Some(task) if task.status == DownloadStatus::Completed => {
+            files::serve_file(&task.save_path, &headers).await
+        }
+        _ => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+// --- LIVE PROGRESS ---
+
+/// Streams `ProgressEvent`s pushed by the engine's reporter as Server-Sent
+/// Events, so the frontend doesn't have to poll `/api/downloads`. Lagged
+/// subscribers just miss the events they fell behind on; the next tick
+/// resyncs tasks that are still downloading, but a missed final status event
+/// (Paused, Completed, Error) is not sent again.
+async fn download_events(
+    Extension(events): Extension<ProgressBroadcaster>,
+) -> Sse<impl Stream<Item = Result<Event, serde_json::Error>>> {
+    let stream = BroadcastStream::new(events.subscribe())
+        .filter_map(|msg| msg.ok())
+        .map(|event| Event::default().json_data(&event));
+
+    Sse::new(stream).keep_alive(
+        KeepAlive::new()
+            .interval(Duration::from_secs(15))
+            .text("keep-alive"),
+    )
+}
@@ -0,0 +1,126 @@
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Share-link lifetime when the caller doesn't specify one.
+pub const DEFAULT_TTL_SECS: u64 = 3600;
+
+#[derive(Debug, Clone)]
+pub struct ShareLink {
+    pub task_id: String,
+    pub expires_at: u64,
+    pub remaining_downloads: Option<u32>,
+}
+
+/// Token -> share link. Expired or exhausted entries are evicted lazily on
+/// lookup rather than via a background sweep.
+pub type ShareRegistry = Arc<DashMap<String, ShareLink>>;
+
+pub enum ShareError {
+    Gone,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Mints a new, random share token for `task_id`, valid for `ttl_secs`
+/// seconds and usable at most `max_downloads` times if given.
+pub fn mint(
+    registry: &ShareRegistry,
+    task_id: String,
+    ttl_secs: u64,
+    max_downloads: Option<u32>,
+) -> String {
+    let token = Uuid::new_v4().simple().to_string();
+    registry.insert(
+        token.clone(),
+        ShareLink {
+            task_id,
+            expires_at: now_secs() + ttl_secs,
+            remaining_downloads: max_downloads,
+        },
+    );
+    token
+}
+
+/// Resolves `token` to its task id, consuming one download against the
+/// remaining-count if one was set. The link is evicted once it's expired or
+/// its counter hits zero, after which lookups report `ShareError::Gone`.
+pub fn consume(registry: &ShareRegistry, token: &str) -> Result<String, ShareError> {
+    let mut entry = registry.get_mut(token).ok_or(ShareError::Gone)?;
+
+    if entry.expires_at <= now_secs() {
+        drop(entry);
+        registry.remove(token);
+        return Err(ShareError::Gone);
+    }
+
+    if entry.remaining_downloads == Some(0) {
+        drop(entry);
+        registry.remove(token);
+        return Err(ShareError::Gone);
+    }
+
+    if let Some(remaining) = entry.remaining_downloads.as_mut() {
+        *remaining -= 1;
+    }
+    let task_id = entry.task_id.clone();
+    let exhausted = entry.remaining_downloads == Some(0);
+    drop(entry);
+
+    if exhausted {
+        registry.remove(token);
+    }
+    Ok(task_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> ShareRegistry {
+        Arc::new(DashMap::new())
+    }
+
+    #[test]
+    fn unknown_token_is_gone() {
+        let registry = registry();
+        assert!(matches!(
+            consume(&registry, "does-not-exist"),
+            Err(ShareError::Gone)
+        ));
+    }
+
+    #[test]
+    fn consumes_a_fresh_unlimited_link_repeatedly() {
+        let registry = registry();
+        let token = mint(&registry, "task-1".to_string(), DEFAULT_TTL_SECS, None);
+
+        assert_eq!(consume(&registry, &token).unwrap(), "task-1");
+        assert_eq!(consume(&registry, &token).unwrap(), "task-1");
+    }
+
+    #[test]
+    fn expired_link_is_gone() {
+        let registry = registry();
+        // A zero-second TTL is already expired the instant it's minted.
+        let token = mint(&registry, "task-1".to_string(), 0, None);
+
+        assert!(matches!(consume(&registry, &token), Err(ShareError::Gone)));
+    }
+
+    #[test]
+    fn link_is_gone_once_its_download_count_is_exhausted() {
+        let registry = registry();
+        let token = mint(&registry, "task-1".to_string(), DEFAULT_TTL_SECS, Some(2));
+
+        assert_eq!(consume(&registry, &token).unwrap(), "task-1");
+        assert_eq!(consume(&registry, &token).unwrap(), "task-1");
+        assert!(matches!(consume(&registry, &token), Err(ShareError::Gone)));
+    }
+}
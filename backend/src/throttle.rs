@@ -0,0 +1,113 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A simple token-bucket bandwidth limiter shared by every chunk writer
+/// across every active download. Tokens are replenished once a second up to
+/// the configured byte rate; `acquire` draws whatever tokens are available
+/// and sleeps in short increments until the rest of the write is covered,
+/// so a burst just pays the difference in latency rather than going over
+/// the cap.
+pub struct Throttle {
+    limit_per_sec: u64,
+    available: AtomicU64,
+}
+
+/// `None` means unthrottled — the common case when `bandwidth_limit_bytes_per_sec`
+/// isn't set in `config.toml`.
+pub type SharedThrottle = Option<Arc<Throttle>>;
+
+impl Throttle {
+    fn new(limit_per_sec: u64) -> Arc<Self> {
+        let throttle = Arc::new(Self {
+            limit_per_sec,
+            available: AtomicU64::new(limit_per_sec),
+        });
+        let refill = throttle.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                refill.available.store(refill.limit_per_sec, Ordering::Relaxed);
+            }
+        });
+        throttle
+    }
+
+    /// Blocks until `bytes` worth of budget is available. Uses a CAS loop
+    /// rather than load-then-subtract: with several chunk workers sharing
+    /// one `Throttle`, two workers racing a plain `fetch_sub` off the same
+    /// observed `available` could subtract more than what was actually
+    /// there and underflow the counter.
+    pub async fn acquire(&self, mut bytes: u64) {
+        while bytes > 0 {
+            let available = self.available.load(Ordering::Relaxed);
+            if available == 0 {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                continue;
+            }
+            let take = bytes.min(available);
+            if self
+                .available
+                .compare_exchange(
+                    available,
+                    available - take,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                bytes -= take;
+            }
+            // Lost the race to another worker — retry with the original
+            // `bytes` against a freshly loaded `available`.
+        }
+    }
+}
+
+/// Builds a throttle from the configured limit, or `None` if downloads
+/// should run unthrottled. Call this once at server startup and share the
+/// result across every task — a `Throttle` per download would let each
+/// concurrent transfer use the full configured rate, making the real
+/// aggregate cap `N ×` what was configured.
+pub fn from_config(limit: Option<u64>) -> SharedThrottle {
+    limit.filter(|&l| l > 0).map(Throttle::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU64 as StdAtomicU64;
+
+    #[tokio::test]
+    async fn acquire_under_budget_does_not_block() {
+        let throttle = Throttle::new(1_000);
+        throttle.acquire(100).await;
+        assert_eq!(throttle.available.load(Ordering::Relaxed), 900);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    async fn concurrent_acquires_never_oversubscribe_the_bucket() {
+        let throttle = Throttle::new(10_000);
+        let drawn = Arc::new(StdAtomicU64::new(0));
+
+        let mut handles = Vec::new();
+        for _ in 0..8 {
+            let throttle = throttle.clone();
+            let drawn = drawn.clone();
+            handles.push(tokio::spawn(async move {
+                for _ in 0..100 {
+                    throttle.acquire(10).await;
+                    drawn.fetch_add(10, Ordering::Relaxed);
+                }
+            }));
+        }
+        for h in handles {
+            h.await.unwrap();
+        }
+
+        // 8 workers * 100 iterations * 10 bytes == 8,000 of the 10,000-byte
+        // bucket, leaving exactly 2,000.
+        assert_eq!(drawn.load(Ordering::Relaxed), 8_000);
+        assert_eq!(throttle.available.load(Ordering::Relaxed), 2_000);
+    }
+}